@@ -1,18 +1,255 @@
 use std::fmt::Debug;
 use serde::{Serialize, Deserialize};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::fs;
 use sha256::digest;
-use std::fs::metadata;
-use std::io::{Error, ErrorKind};
+use std::io::{Error, ErrorKind, Read, Write};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::cell::RefCell;
 
-#[derive(Serialize, Deserialize, Debug)]
+/// The number of raw `.value` JSON strings kept in the read cache when a capacity
+/// is not explicitly provided via [`KVStore::with_cache_capacity`].
+const DEFAULT_CACHE_CAPACITY: usize = 128;
+
+/// Filenames skipped when enumerating buckets and counting existing pairs,
+/// so non-store files dropped into the directory (by an OS or an editor)
+/// don't get walked into or miscounted, when no explicit list is given via
+/// [`KVStore::with_options`].
+const DEFAULT_IGNORED_NAMES: &[&str] = &["thumbs.db", ".DS_Store", "target"];
+
+fn default_ignored_names() -> Vec<String> {
+    DEFAULT_IGNORED_NAMES.iter().map(|name| name.to_string()).collect()
+}
+
+/// Identifies a file written by [`KVStore::export`] so [`KVStore::import`]
+/// can reject anything else early.
+const ARCHIVE_MAGIC: &[u8; 4] = b"KVS1";
+
+/// An entry in an archive's table of contents: the byte offset and length of
+/// a pair's `.key` and `.value` blobs, relative to the start of the blob
+/// region (i.e. right after the table of contents itself).
+#[derive(Serialize, Deserialize)]
+struct ArchiveEntry {
+    hash: String,
+    key_offset: u64,
+    key_len: u64,
+    value_offset: u64,
+    value_len: u64,
+}
+
+/// The table of contents written right after the archive header, mapping
+/// every key-hash to where its blobs live in the archive.
+#[derive(Serialize, Deserialize)]
+struct ArchiveIndex {
+    entries: Vec<ArchiveEntry>,
+}
+
+/// Abstracts the handful of filesystem operations `KVStore` relies on. The
+/// default [`DiskBackend`] wraps `std::fs`; [`InMemoryBackend`] keeps
+/// everything in a `HashMap` so the fuzz target and tests can exercise
+/// insert/lookup/remove at high throughput with no disk I/O or leftover
+/// files.
+pub trait Backend {
+    fn create_dir_all(&self, path: &Path) -> std::io::Result<()>;
+    fn create_dir(&self, path: &Path) -> std::io::Result<()>;
+    /// Returns the paths of the direct children of `path`.
+    fn read_dir(&self, path: &Path) -> std::io::Result<Vec<PathBuf>>;
+    fn write(&self, path: &Path, contents: &[u8]) -> std::io::Result<()>;
+    fn read(&self, path: &Path) -> std::io::Result<Vec<u8>>;
+    fn read_to_string(&self, path: &Path) -> std::io::Result<String> {
+        let bytes = self.read(path)?;
+        String::from_utf8(bytes).map_err(|error| Error::new(ErrorKind::InvalidData, error))
+    }
+    fn remove_file(&self, path: &Path) -> std::io::Result<()>;
+    fn remove_dir_all(&self, path: &Path) -> std::io::Result<()>;
+    fn rename(&self, from: &Path, to: &Path) -> std::io::Result<()>;
+    fn exists(&self, path: &Path) -> bool;
+    fn is_dir(&self, path: &Path) -> bool;
+    /// Restricts `path` to owner read/write where the backend supports file
+    /// permissions. A no-op for backends (like [`InMemoryBackend`]) that don't.
+    fn set_owner_only(&self, path: &Path) -> std::io::Result<()>;
+}
+
+/// Restricts `path` to owner read/write (mode `0o600`) on Unix, mirroring how
+/// secret-keystore directories lock down the files they write. A no-op on
+/// other platforms.
+#[cfg(unix)]
+fn restrict_to_owner(path: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))
+}
+
+#[cfg(not(unix))]
+fn restrict_to_owner(_path: &Path) -> std::io::Result<()> {
+    Ok(())
+}
+
+/// The default [`Backend`], a thin wrapper around `std::fs`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DiskBackend;
+
+impl Backend for DiskBackend {
+    fn create_dir_all(&self, path: &Path) -> std::io::Result<()> {
+        fs::create_dir_all(path)
+    }
+
+    fn create_dir(&self, path: &Path) -> std::io::Result<()> {
+        fs::create_dir(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> std::io::Result<Vec<PathBuf>> {
+        fs::read_dir(path)?.map(|entry| entry.map(|entry| entry.path())).collect()
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> std::io::Result<()> {
+        fs::write(path, contents)
+    }
+
+    fn read(&self, path: &Path) -> std::io::Result<Vec<u8>> {
+        fs::read(path)
+    }
+
+    fn read_to_string(&self, path: &Path) -> std::io::Result<String> {
+        fs::read_to_string(path)
+    }
+
+    fn remove_file(&self, path: &Path) -> std::io::Result<()> {
+        fs::remove_file(path)
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> std::io::Result<()> {
+        fs::remove_dir_all(path)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> std::io::Result<()> {
+        fs::rename(from, to)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        path.is_dir()
+    }
+
+    fn set_owner_only(&self, path: &Path) -> std::io::Result<()> {
+        restrict_to_owner(path)
+    }
+}
+
+/// An in-memory [`Backend`] built on a `HashMap<PathBuf, Vec<u8>>`, with a
+/// parallel set of known directories for bookkeeping, so tests and fuzzing
+/// can exercise `KVStore` entirely in RAM.
+#[derive(Debug, Default)]
+pub struct InMemoryBackend {
+    files: RefCell<HashMap<PathBuf, Vec<u8>>>,
+    dirs: RefCell<HashSet<PathBuf>>,
+}
+
+impl Backend for InMemoryBackend {
+    fn create_dir_all(&self, path: &Path) -> std::io::Result<()> {
+        let mut current = PathBuf::new();
+        for component in path.components() {
+            current.push(component);
+            self.dirs.borrow_mut().insert(current.clone());
+        }
+        Ok(())
+    }
+
+    fn create_dir(&self, path: &Path) -> std::io::Result<()> {
+        self.dirs.borrow_mut().insert(path.to_path_buf());
+        Ok(())
+    }
+
+    fn read_dir(&self, path: &Path) -> std::io::Result<Vec<PathBuf>> {
+        if !self.is_dir(path) {
+            return Err(Error::new(ErrorKind::NotFound, "No such directory in in-memory backend."));
+        }
+        let mut entries: HashSet<PathBuf> = HashSet::new();
+        for file_path in self.files.borrow().keys() {
+            if file_path.parent() == Some(path) {
+                entries.insert(file_path.clone());
+            }
+        }
+        for dir_path in self.dirs.borrow().iter() {
+            if dir_path.parent() == Some(path) {
+                entries.insert(dir_path.clone());
+            }
+        }
+        Ok(entries.into_iter().collect())
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> std::io::Result<()> {
+        self.files.borrow_mut().insert(path.to_path_buf(), contents.to_vec());
+        Ok(())
+    }
+
+    fn read(&self, path: &Path) -> std::io::Result<Vec<u8>> {
+        self.files
+            .borrow()
+            .get(path)
+            .cloned()
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "No such file in in-memory backend."))
+    }
+
+    fn remove_file(&self, path: &Path) -> std::io::Result<()> {
+        self.files
+            .borrow_mut()
+            .remove(path)
+            .map(|_| ())
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "No such file in in-memory backend."))
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> std::io::Result<()> {
+        self.files.borrow_mut().retain(|file_path, _| !file_path.starts_with(path));
+        self.dirs.borrow_mut().retain(|dir_path| !dir_path.starts_with(path));
+        Ok(())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> std::io::Result<()> {
+        let contents = self
+            .files
+            .borrow_mut()
+            .remove(from)
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "No such file in in-memory backend."))?;
+        self.files.borrow_mut().insert(to.to_path_buf(), contents);
+        Ok(())
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.files.borrow().contains_key(path) || self.dirs.borrow().contains(path)
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.dirs.borrow().contains(path)
+    }
+
+    fn set_owner_only(&self, _path: &Path) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
 /// A struct that represents a key-value store.
-pub struct KVStore {
+pub struct KVStore<B: Backend = DiskBackend> {
     /// The number of key-value mappings currently stored.
     size: usize,
     /// The location of the file system where key-value mappings are stored.
     path: String,
+    /// A read cache holding the raw `.value` JSON for recently accessed keys,
+    /// keyed by the SHA256 digest of the serialized key.
+    cache: HashMap<String, String>,
+    /// Tracks cache access order, most-recently-used at the front, so the
+    /// least-recently-used entry can be evicted once `cache_cap` is exceeded.
+    cache_usage: VecDeque<String>,
+    /// The maximum number of entries kept in `cache`.
+    cache_cap: usize,
+    /// Filenames skipped when enumerating buckets and counting pairs.
+    ignored_names: Vec<String>,
+    /// The storage backend every filesystem operation is routed through.
+    backend: B,
 }
 
 /// A trait that defines the operations that need to be supported.
@@ -69,11 +306,14 @@ pub trait Operations {
     ///
     /// Refer to [https://docs.serde.rs/serde/](https://docs.serde.rs/serde/)
     /// and [https://serde.rs](https://serde.rs) for serde.
-    fn lookup<K, V>(self: &Self, key: K) -> std::io::Result<V>
+    ///
+    /// A hit against the internal read cache also refreshes that entry's
+    /// position in the LRU order, so `lookup` takes `self` mutably.
+    fn lookup<K, V>(self: &mut Self, key: K) -> std::io::Result<V>
     where
         K: serde::Serialize + Default + Debug,
         V: serde::de::DeserializeOwned + Default + Debug;
-    
+
     /// A function that removes a previously-inserted key-value mapping.
     ///
     /// If there **is** a key-value mapping stored already with the same key, it should return
@@ -95,58 +335,63 @@ pub trait Operations {
         V: serde::de::DeserializeOwned + Default + Debug;
 }
 
-fn create_file_path<'a>(path: &String, hashed_value: &'a str, extension: &'a str) -> String {
-    let file_path = match path.as_str() {
-        "." => format!("{}{}{}", "/", &hashed_value, extension),
-        _ => format!("{}{}{}{}", path, "/", &hashed_value, extension),
-    };
-
-    file_path
-}
-
 fn combine_string<'a>(first: &'a str, second: &'a str) -> String {
     format!("{}{}", first, second)
 }
 
-impl Operations for KVStore {
-    fn new(path: &str) -> std::io::Result<Self> {
+/// Appends a trailing `/` to `path` if it doesn't already have one, so every
+/// bucket path built from it can simply be concatenated.
+fn sanitize_path(path: &str) -> String {
+    let mut sanitized_path = String::from(path);
+    let length = sanitized_path.len();
+    let last_char = &sanitized_path[length-1..];
+    if !last_char.contains(&String::from("/")){
+        sanitized_path = sanitized_path + "/";
+    }
+    sanitized_path
+}
+
+impl<B: Backend> KVStore<B> {
+    /// Initializes a KVStore the same way [`Operations::new`] does, but lets
+    /// the caller plug in a [`Backend`] and fully configure the read cache
+    /// capacity and ignore-list, instead of using the defaults.
+    pub fn with_full_options(path: &str, cache_cap: usize, ignored_names: Vec<String>, backend: B) -> std::io::Result<Self> {
         //let check_dir = Path::new(path).read_dir()?;    //checks dir existence.
-        fs::create_dir_all(&path)?;                 //creates dir at path. if error, returns std error.
+        backend.create_dir_all(Path::new(path))?;   //creates dir at path. if error, returns std error.
         //TODO: should we exclude target from possible directories creation?
-        let is_empty = Path::new(path).read_dir()?.next().is_none();
+        let is_empty = backend.read_dir(Path::new(path))?.is_empty();
         //println!("{}",is_empty);
-        
-        let mut sanitized_path = String::from(path);    //will we need to add a / to the end of the path? 
-        let length = sanitized_path.len();
-        let last_char = &sanitized_path[length-1..];    //https://stackoverflow.com/questions/48642342/how-to-get-the-last-character-of-a-str
-        //println!("{}",last_char);
-        if !last_char.contains(&String::from("/")){     //if it does not contain a /, it will need to be added to the sanitized path
-            sanitized_path = sanitized_path + "/";
-        }
+
+        let sanitized_path = sanitize_path(path);    //will we need to add a / to the end of the path?
         match is_empty {
             true => {                                   //no existing key-value mappings
                 let new_kvstore = KVStore {
                     size: 0,
                     path: sanitized_path,
+                    cache: HashMap::new(),
+                    cache_usage: VecDeque::new(),
+                    cache_cap,
+                    ignored_names,
+                    backend,
                 };
                 Ok(new_kvstore)
             },
-            false => {  
+            false => {
                 let mut counter = 0;
-                for entry in fs::read_dir(path)? {      //grabs all entries in the directory and searches for ".key"
-                    let entry = entry?;                 //counting all the KV pairs in the directory
-                    //let filename = entry.file_name().into_string();   //to initialize a KVStore instance with an existing number of pairs
-                    let pathname = entry.path();            //https://doc.rust-lang.org/std/fs/struct.DirEntry.html#method.path
-                    let filename = pathname.to_str().unwrap();
-                    let file_metadata = metadata(filename).unwrap();    //https://stackoverflow.com/questions/30309100/how-to-check-if-a-given-path-is-a-file-or-directory
-                    if file_metadata.is_dir() {     //beginning of sub directory check for keyvalue pairs
-
-                        for entry in fs::read_dir(filename)? {      
-                            let entry = entry?;                 
-                            let pathname = entry.path();            
-                            let filename2 = pathname.to_str().unwrap();
-                            if filename2.contains(&String::from(".key")) {
-                                counter = counter + 1;       
+                for bucket_path in backend.read_dir(Path::new(path))? {      //grabs all entries in the directory and searches for ".key"
+                    let filename = bucket_path.to_str().unwrap();
+                    let bucket_name = bucket_path.file_name().unwrap().to_str().unwrap();
+                    if ignored_names.iter().any(|ignored| ignored == bucket_name) {
+                        continue;
+                    }
+                    if backend.is_dir(&bucket_path) {     //beginning of sub directory check for keyvalue pairs
+                        for entry_path in backend.read_dir(&bucket_path)? {
+                            let pair_name = entry_path.file_name().unwrap().to_str().unwrap();
+                            if ignored_names.iter().any(|ignored| ignored == pair_name) {
+                                continue;
+                            }
+                            if entry_path.extension().and_then(|ext| ext.to_str()) == Some("key") {
+                                counter = counter + 1;
                             }
                         }
                     }
@@ -156,11 +401,254 @@ impl Operations for KVStore {
                 let new_kvstore = KVStore {             //create instance of KVStore to account for existing and new key value pairs
                     size: counter,
                     path: sanitized_path,
+                    cache: HashMap::new(),
+                    cache_usage: VecDeque::new(),
+                    cache_cap,
+                    ignored_names,
+                    backend,
                 };
                 Ok(new_kvstore)
             }
         }
-        
+
+    }
+
+    /// Initializes a KVStore the same way [`Operations::new`] does, but lets
+    /// the caller plug in a [`Backend`] (e.g. [`InMemoryBackend`] for tests
+    /// and fuzzing), using the default cache capacity and ignore-list.
+    pub fn with_backend(path: &str, backend: B) -> std::io::Result<Self> {
+        KVStore::with_full_options(path, DEFAULT_CACHE_CAPACITY, default_ignored_names(), backend)
+    }
+
+    /// Records `value` as the most-recently-used entry for `hashed_key`,
+    /// evicting the least-recently-used entry once `cache_cap` is exceeded.
+    fn cache_insert(&mut self, hashed_key: &str, value: String) {
+        if let Some(position) = self.cache_usage.iter().position(|k| k == hashed_key) {
+            self.cache_usage.remove(position);
+        }
+        self.cache_usage.push_front(hashed_key.to_string());
+        self.cache.insert(hashed_key.to_string(), value);
+        while self.cache.len() > self.cache_cap {
+            if let Some(oldest) = self.cache_usage.pop_back() {
+                self.cache.remove(&oldest);
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Removes `hashed_key` from both the cache map and the usage deque.
+    fn cache_remove(&mut self, hashed_key: &str) {
+        self.cache.remove(hashed_key);
+        if let Some(position) = self.cache_usage.iter().position(|k| k == hashed_key) {
+            self.cache_usage.remove(position);
+        }
+    }
+
+    /// Computes the bucket subdirectory for `hashed_key` directly from the
+    /// first 10 hash characters, instead of scanning `self.path` looking for
+    /// a subdirectory whose name matches.
+    fn bucket_path(&self, hashed_key: &str) -> PathBuf {
+        Path::new(&self.path).join(&hashed_key[0..10])
+    }
+
+    /// Computes the `.key`/`.value` file paths for `hashed_key` inside its bucket.
+    fn key_paths(&self, hashed_key: &str) -> (PathBuf, PathBuf) {
+        let bucket_path = self.bucket_path(hashed_key);
+        (
+            bucket_path.join(combine_string(hashed_key, ".key")),
+            bucket_path.join(combine_string(hashed_key, ".value")),
+        )
+    }
+
+    /// Picks a filename in `dir` that does not exist yet by appending a short
+    /// random suffix to `base_name`, retrying until an unused name is found.
+    /// Writing payloads here first and renaming them into place afterwards is
+    /// what makes `insert` atomic on the same filesystem.
+    fn unique_temp_path(&self, dir: &Path, base_name: &str) -> PathBuf {
+        loop {
+            let nanos = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .subsec_nanos();
+            let suffix = format!("{:x}{:x}", std::process::id(), nanos);
+            let candidate = dir.join(combine_string(base_name, &combine_string(".tmp", &suffix)));
+            if !self.backend.exists(&candidate) {
+                return candidate;
+            }
+        }
+    }
+
+    /// Walks every bucket subdirectory under `self.path` and returns the raw
+    /// JSON text stored in each `.key` file, without deserializing it to any
+    /// particular key type.
+    pub fn keys(&self) -> std::io::Result<Vec<String>> {
+        let mut keys = Vec::new();
+        for bucket_path in self.backend.read_dir(Path::new(&self.path))? {
+            let bucket_name = bucket_path.file_name().unwrap().to_str().unwrap();
+            if self.ignored_names.iter().any(|ignored| ignored == bucket_name) {
+                continue;
+            }
+            if !self.backend.is_dir(&bucket_path) {
+                continue;
+            }
+            for entry_path in self.backend.read_dir(&bucket_path)? {
+                let entry_name = entry_path.file_name().unwrap().to_str().unwrap();
+                if self.ignored_names.iter().any(|ignored| ignored == entry_name) {
+                    continue;
+                }
+                if entry_path.extension().and_then(|ext| ext.to_str()) == Some("key") {
+                    keys.push(self.backend.read_to_string(&entry_path)?);
+                }
+            }
+        }
+        Ok(keys)
+    }
+
+    /// Walks every bucket subdirectory, deserializing each matched `.key`/`.value`
+    /// pair. `V` has the same serde bound as in [`Operations::lookup`]; `K` must
+    /// be `DeserializeOwned` rather than `Serialize`, since keys are read back.
+    pub fn iter<K, V>(&self) -> std::io::Result<Vec<(K, V)>>
+    where
+        K: serde::de::DeserializeOwned + Default + Debug,
+        V: serde::de::DeserializeOwned + Default + Debug,
+    {
+        let mut pairs = Vec::new();
+        for bucket_path in self.backend.read_dir(Path::new(&self.path))? {
+            let bucket_name = bucket_path.file_name().unwrap().to_str().unwrap();
+            if self.ignored_names.iter().any(|ignored| ignored == bucket_name) {
+                continue;
+            }
+            if !self.backend.is_dir(&bucket_path) {
+                continue;
+            }
+            for key_path in self.backend.read_dir(&bucket_path)? {
+                let key_name = key_path.file_name().unwrap().to_str().unwrap();
+                if self.ignored_names.iter().any(|ignored| ignored == key_name) {
+                    continue;
+                }
+                if key_path.extension().and_then(|ext| ext.to_str()) != Some("key") {
+                    continue;
+                }
+                let value_path = key_path.with_extension("value");
+                let key_contents = self.backend.read_to_string(&key_path)?;
+                let value_contents = self.backend.read_to_string(&value_path)?;
+                let key: K = serde_json::from_str(&key_contents)?;
+                let value: V = serde_json::from_str(&value_contents)?;
+                pairs.push((key, value));
+            }
+        }
+        Ok(pairs)
+    }
+
+    /// Bundles every key-value pair into one portable archive: a magic number,
+    /// a length-prefixed JSON table of contents (see [`ArchiveIndex`]), then
+    /// the raw `.key`/`.value` blobs concatenated in table-of-contents order.
+    pub fn export<W: Write>(&self, mut out: W) -> std::io::Result<()> {
+        let mut raw_pairs: Vec<(String, Vec<u8>, Vec<u8>)> = Vec::new();
+        for bucket_path in self.backend.read_dir(Path::new(&self.path))? {
+            let bucket_name = bucket_path.file_name().unwrap().to_str().unwrap();
+            if self.ignored_names.iter().any(|ignored| ignored == bucket_name) {
+                continue;
+            }
+            if !self.backend.is_dir(&bucket_path) {
+                continue;
+            }
+            for key_path in self.backend.read_dir(&bucket_path)? {
+                let key_name = key_path.file_name().unwrap().to_str().unwrap();
+                if self.ignored_names.iter().any(|ignored| ignored == key_name) {
+                    continue;
+                }
+                if key_path.extension().and_then(|ext| ext.to_str()) != Some("key") {
+                    continue;
+                }
+                let value_path = key_path.with_extension("value");
+                let hash = key_path.file_stem().unwrap().to_str().unwrap().to_string();
+                let key_bytes = self.backend.read(&key_path)?;
+                let value_bytes = self.backend.read(&value_path)?;
+                raw_pairs.push((hash, key_bytes, value_bytes));
+            }
+        }
+
+        let mut entries = Vec::with_capacity(raw_pairs.len());
+        let mut offset: u64 = 0;
+        for (hash, key_bytes, value_bytes) in &raw_pairs {
+            let key_offset = offset;
+            let key_len = key_bytes.len() as u64;
+            offset += key_len;
+            let value_offset = offset;
+            let value_len = value_bytes.len() as u64;
+            offset += value_len;
+            entries.push(ArchiveEntry {
+                hash: hash.clone(),
+                key_offset,
+                key_len,
+                value_offset,
+                value_len,
+            });
+        }
+        let index_bytes = serde_json::to_vec(&ArchiveIndex { entries })?;
+
+        out.write_all(ARCHIVE_MAGIC)?;
+        out.write_all(&(index_bytes.len() as u64).to_le_bytes())?;
+        out.write_all(&index_bytes)?;
+        for (_, key_bytes, value_bytes) in &raw_pairs {
+            out.write_all(key_bytes)?;
+            out.write_all(value_bytes)?;
+        }
+        Ok(())
+    }
+
+    /// Reconstructs a KVStore at `path` from an archive written by [`KVStore::export`],
+    /// recreating the first-10-hash subdirectory layout through `backend` and returning a
+    /// ready KVStore with `size` set from the table of contents' entry count.
+    pub fn import<R: Read>(path: &str, mut input: R, backend: B) -> std::io::Result<Self> {
+        let mut magic = [0u8; 4];
+        input.read_exact(&mut magic)?;
+        if &magic != ARCHIVE_MAGIC {
+            return Err(Error::new(ErrorKind::InvalidData, "Not a valid key-value store archive."));
+        }
+        let mut index_len_bytes = [0u8; 8];
+        input.read_exact(&mut index_len_bytes)?;
+        let index_len = u64::from_le_bytes(index_len_bytes) as usize;
+        let mut index_bytes = vec![0u8; index_len];
+        input.read_exact(&mut index_bytes)?;
+        let index: ArchiveIndex = serde_json::from_slice(&index_bytes)?;
+
+        backend.create_dir_all(Path::new(path))?;
+        let sanitized_path = sanitize_path(path);
+
+        for entry in &index.entries {
+            let mut key_bytes = vec![0u8; entry.key_len as usize];
+            input.read_exact(&mut key_bytes)?;
+            let mut value_bytes = vec![0u8; entry.value_len as usize];
+            input.read_exact(&mut value_bytes)?;
+
+            let bucket_path = Path::new(&sanitized_path).join(&entry.hash[0..10]);
+            backend.create_dir_all(&bucket_path)?;
+            let key_file_path = bucket_path.join(combine_string(&entry.hash, ".key"));
+            let value_file_path = bucket_path.join(combine_string(&entry.hash, ".value"));
+            backend.write(&key_file_path, &key_bytes)?;
+            backend.write(&value_file_path, &value_bytes)?;
+            backend.set_owner_only(&key_file_path)?;
+            backend.set_owner_only(&value_file_path)?;
+        }
+
+        Ok(KVStore {
+            size: index.entries.len(),
+            path: sanitized_path,
+            cache: HashMap::new(),
+            cache_usage: VecDeque::new(),
+            cache_cap: DEFAULT_CACHE_CAPACITY,
+            ignored_names: default_ignored_names(),
+            backend,
+        })
+    }
+}
+
+impl<B: Backend + Default> Operations for KVStore<B> {
+    fn new(path: &str) -> std::io::Result<Self> {
+        KVStore::with_full_options(path, DEFAULT_CACHE_CAPACITY, default_ignored_names(), B::default())
     }
 
     fn size(self: &Self) -> usize {
@@ -171,55 +659,44 @@ impl Operations for KVStore {
     where
         K: serde::Serialize + Default + Debug,
         V: serde::Serialize + Default + Debug,
-    {        
+    {
         let serialize_key = serde_json::to_string(&key).unwrap();
         let serialize_value = serde_json::to_string(&value).unwrap();
         let hashed_key = digest(&serialize_key);
-        let key_file_name = combine_string(&hashed_key, ".key");
-        let first_ten_key = &hashed_key[0..10];
-        let desired_subdirectory_path = combine_string(&self.path, &first_ten_key);
-
-        let mut directory_exists = false;
-        for subdirectory_entry in fs::read_dir(&self.path)? {      
-            let subdirectory_entry = subdirectory_entry?;                 
-            let path_name = subdirectory_entry.path();
-            let subdirectory_path = path_name.to_str().unwrap();
-            let subdirectory_name = path_name.file_name().unwrap().to_str().unwrap();
-            
-            if subdirectory_name.len() == 10 {
-                let subdir_ten_key = &subdirectory_name[0..10];
-                let file_metadata = metadata(subdirectory_path).unwrap(); 
-                            
-                if first_ten_key.eq(subdir_ten_key) {
-                    if file_metadata.is_dir() {
-                        for entry in fs::read_dir(subdirectory_path)? {      
-                            let entry = entry?;                 
-                            let path_name = entry.path();            
-                            let file_name = path_name.file_name().unwrap().to_str().unwrap();
-                            
-                            if file_name.eq(&key_file_name) {
-                                let custom_error = Error::new(ErrorKind::AlreadyExists, "There is a key-value mapping stored already with the same key.");
-                                return Err(custom_error);
-                            } 
-                        }
-                    }
-                    directory_exists = true;
-                    break;
-                }
-            }
+        let bucket_path = self.bucket_path(&hashed_key);
+        let (key_file_path, value_file_path) = self.key_paths(&hashed_key);
+
+        if self.backend.exists(&key_file_path) {
+            let custom_error = Error::new(ErrorKind::AlreadyExists, "There is a key-value mapping stored already with the same key.");
+            return Err(custom_error);
         }
-        if !directory_exists {
-            fs::create_dir(&desired_subdirectory_path)?;
+        if !self.backend.exists(&bucket_path) {
+            self.backend.create_dir(&bucket_path)?;
         }
-        let key_file_path = create_file_path(&desired_subdirectory_path, &hashed_key, ".key");
-        let value_file_path = create_file_path(&desired_subdirectory_path, &hashed_key, ".value");
-        fs::write(&key_file_path, serialize_key).expect("Unable to write file");
-        fs::write(&value_file_path, serialize_value).expect("Unable to write file");  
+
+        //write both payloads to uniquely-named temp files first, and only rename
+        //them into place once both are on disk, so a crash between the two
+        //writes never leaves a `.key` with no matching `.value` (or vice versa).
+        //Rename `.value` into place before `.key`: see
+        //insert_never_makes_a_key_visible_without_its_value for why the order matters.
+        let key_file_name = key_file_path.file_name().unwrap().to_str().unwrap().to_string();
+        let value_file_name = value_file_path.file_name().unwrap().to_str().unwrap().to_string();
+        let key_tmp_path = self.unique_temp_path(&bucket_path, &key_file_name);
+        let value_tmp_path = self.unique_temp_path(&bucket_path, &value_file_name);
+        self.backend.write(&key_tmp_path, serialize_key.as_bytes()).expect("Unable to write file");
+        self.backend.write(&value_tmp_path, serialize_value.as_bytes()).expect("Unable to write file");
+
+        self.backend.rename(&value_tmp_path, &value_file_path)?;
+        self.backend.rename(&key_tmp_path, &key_file_path)?;
+        self.backend.set_owner_only(&value_file_path)?;
+        self.backend.set_owner_only(&key_file_path)?;
+
+        self.cache_insert(&hashed_key, serialize_value);
 
         Ok(())
     }
 
-    fn lookup<K, V>(self: &Self, key: K) -> std::io::Result<V>
+    fn lookup<K, V>(self: &mut Self, key: K) -> std::io::Result<V>
     where
         K: serde::Serialize + Default + Debug,
         V: serde::de::DeserializeOwned + Default + Debug
@@ -229,46 +706,28 @@ impl Operations for KVStore {
         //if for loop ends in root level, that means lookup failed, return std error
         let serialize_key = serde_json::to_string(&key).unwrap();
         let hashed_key = digest(&serialize_key);
-        let key_file_name = combine_string(&hashed_key, ".key");
-        let value_file_name = combine_string(&hashed_key, ".value");
-        for subdirectory in fs::read_dir(&self.path)? {
-            let subdirectory = subdirectory?;
-            let path_name = subdirectory.path();
-            let subdirectory_path = path_name.to_str().unwrap();        //subdirectory path name should be first 10 sha digits
-            let subdirectory_name = path_name.file_name().unwrap().to_str().unwrap(); //raw filename
-            if subdirectory_name.len() < 10 {
-                //println!("{} too small",subdirectory_name);
-                continue;
+
+        if let Some(cached) = self.cache.get(&hashed_key).cloned() {
+            if let Some(position) = self.cache_usage.iter().position(|k| k == &hashed_key) {
+                self.cache_usage.remove(position);
             }
-            let subdir_ten_key = &subdirectory_name[0..10];                 //extract first 10 digits of hashed key to compare with subdir names
-            let first_ten_key = &hashed_key[0..10];
-            let file_metadata = metadata(subdirectory_path).unwrap();
-
-            if first_ten_key.eq(subdir_ten_key) {
-                if file_metadata.is_dir() {
-                    for entry in fs::read_dir(subdirectory_path)?{      //iterating through sub directory
-                        let entry = entry?;                 
-                        let path_name = entry.path();            
-                        let file_name = path_name.file_name().unwrap().to_str().unwrap();
-
-                        if file_name.eq(&value_file_name){                //have found desired key in lookup by finding its corresponding sha256string.value file
-                            let entire_file_path = format!("{}{}{}{}", subdirectory_path, "/" ,&hashed_key, ".value");  //concantenate file's path
-                            //println!("{}",entire_file_path);
-                            let contents = fs::read_to_string(entire_file_path)?;      //returns Result<string>, so unwrap;
-                            let deserialize_value = serde_json::from_str(&contents)?;   //deserialize
-                            //println!("{:?} is deserial",deserialize_value);
-                            return Ok(deserialize_value);
-                        }
-                    }
-                    //key did not exist in subdirectory and it can't exist anywhere else
-                    let custom_error = Error::new(ErrorKind::NotFound, "No key-value mapping exists with this key.");
-                    return Err(custom_error);
+            self.cache_usage.push_front(hashed_key.clone());
+            let deserialize_value = serde_json::from_str(&cached)?;
+            return Ok(deserialize_value);
+        }
 
-                }
+        let (_, value_file_path) = self.key_paths(&hashed_key);
+        match self.backend.read_to_string(&value_file_path) {
+            Ok(contents) => {
+                let deserialize_value = serde_json::from_str(&contents)?;   //deserialize
+                self.cache_insert(&hashed_key, contents);
+                Ok(deserialize_value)
+            }
+            Err(error) if error.kind() == ErrorKind::NotFound => {
+                Err(Error::new(ErrorKind::NotFound, "No key-value mapping exists with this key."))
             }
+            Err(error) => Err(error),
         }
-        let custom_error = Error::new(ErrorKind::NotFound, "Finished root level directory with no key matches.");       //no subdirectories or something wrong with accessing directory
-        Err(custom_error)   
     }
 
     fn remove<K, V>(self: &mut Self, key: K) -> std::io::Result<V>
@@ -278,65 +737,63 @@ impl Operations for KVStore {
     {
         let serialize_key = serde_json::to_string(&key).unwrap();
         let hashed_key = digest(&serialize_key);
-        let key_file_name = combine_string(&hashed_key, ".key");
-        let value_file_name = combine_string(&hashed_key, ".value");
-        for subdirectory in fs::read_dir(&self.path)? {
-            let subdirectory = subdirectory?;
-            let path_name = subdirectory.path();
-            let subdirectory_path = path_name.to_str().unwrap();        //subdirectory path name should be first 10 sha digits
-            let subdirectory_name = path_name.file_name().unwrap().to_str().unwrap(); //raw filename
-            if subdirectory_name.len() < 10 {                           //technically we should not need this b/c all inserts will be 10 digit sha dirs
-                //println!("{} too small",subdirectory_name);
-                continue;
+        let bucket_path = self.bucket_path(&hashed_key);
+        let (key_file_path, value_file_path) = self.key_paths(&hashed_key);
+
+        let contents = match self.backend.read_to_string(&value_file_path) {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == ErrorKind::NotFound => {
+                let custom_error = Error::new(ErrorKind::NotFound, "No key-value mapping exists with this key, failed remove.");
+                return Err(custom_error);
             }
-            let subdir_ten_key = &subdirectory_name[0..10];                 //extract first 10 digits of hashed key to compare with subdir names
-            let first_ten_key = &hashed_key[0..10];
-            let file_metadata = metadata(subdirectory_path).unwrap();
-
-            if first_ten_key.eq(subdir_ten_key) {
-                if file_metadata.is_dir() {
-                    for entry in fs::read_dir(subdirectory_path)?{      //iterating through sub directory
-                        let entry = entry?;                 
-                        let path_name = entry.path();            
-                        let file_name = path_name.file_name().unwrap().to_str().unwrap();
-            
-                        if file_name.eq(&value_file_name){                //grabs deserialized value and removes .value                
-                
-                            for entry1 in fs::read_dir(subdirectory_path)? {    //implied that key must exist bc we found value, so find it
-                                let entry1 = entry1?;
-                                let path_name1 = entry1.path();            
-                                let file_name1 = path_name1.file_name().unwrap().to_str().unwrap();
-                                if file_name1.eq(&key_file_name) {              //remove key            
-                                    let entire_file_path = format!("{}{}{}{}", subdirectory_path, "/" ,&hashed_key, ".key");    
-                                    println!("removing key {}",entire_file_path);
-                                    fs::remove_file(entire_file_path)?;             
-                                }
-                            }
+            Err(error) => return Err(error),
+        };
+        let deserialize_value = serde_json::from_str(&contents)?;   //deserialize
 
-                            let entire_file_path = format!("{}{}{}{}", subdirectory_path, "/" ,&hashed_key, ".value");  //concantenate file's path
-                            let entire_file_path_remove = String::from(&entire_file_path);
-                            let contents = fs::read_to_string(entire_file_path)?;      //reads contents and returns Result<string>, so unwrap;
-                            let deserialize_value = serde_json::from_str(&contents)?;   //deserialize
-                            println!("removing value {}",entire_file_path_remove);
-                            fs::remove_file(entire_file_path_remove)?;                  //remove value
-
-                            //have found key's corresponding value, now check dir if empty
-                            if Path::new(subdirectory_path).read_dir()?.next().is_none().eq(&true){    //empty directory
-                                println!("empty directory, deleting {}",subdirectory_path);
-                                fs::remove_dir_all(subdirectory_path)?;
-                            }
-                            return Ok(deserialize_value);
-                        }
-                    }
-                    //key did not exist in subdirectory and it can't exist anywhere else
-                    let custom_error = Error::new(ErrorKind::NotFound, "No key-value mapping exists with this key, failed remove.");
-                    return Err(custom_error);
+        println!("removing key {}", key_file_path.display());
+        self.backend.remove_file(&key_file_path)?;
+        println!("removing value {}", value_file_path.display());
+        self.backend.remove_file(&value_file_path)?;
 
-                }
-            }
+        //have found key's corresponding value, now check dir if empty
+        if self.backend.read_dir(&bucket_path)?.is_empty() {    //empty directory
+            println!("empty directory, deleting {}", bucket_path.display());
+            self.backend.remove_dir_all(&bucket_path)?;
         }
-        let custom_error = Error::new(ErrorKind::NotFound, "Finished root level directory with no key matches, failed remove.");       //no subdirectories or something wrong with accessing directory
-        Err(custom_error)   
+        self.cache_remove(&hashed_key);
+        Ok(deserialize_value)
+    }
+}
+
+impl KVStore<DiskBackend> {
+    /// Resolves the default backend for bare `KVStore::new(path)` calls. The
+    /// `B: Backend = DiskBackend` default on the struct only kicks in once the
+    /// type is otherwise pinned down; an unannotated call into the generic
+    /// `impl<B: Backend + Default> Operations for KVStore<B>` block can't
+    /// infer `B` on its own, so this concrete inherent function (which takes
+    /// priority over the trait one) pins it to `DiskBackend` the same way
+    /// `HashMap::new()` pins its hasher to `RandomState`.
+    pub fn new(path: &str) -> std::io::Result<Self> {
+        <Self as Operations>::new(path)
+    }
+
+    /// Initializes a KVStore the same way [`KVStore::new`] does, but lets the
+    /// caller configure how many raw `.value` JSON strings are kept in the read cache
+    /// (see `cache`/`cache_usage` on [`KVStore`]) instead of using
+    /// `DEFAULT_CACHE_CAPACITY`. Uses `DEFAULT_IGNORED_NAMES` for the ignore-list;
+    /// see [`KVStore::with_options`] to also configure that. Pinned to
+    /// `DiskBackend` for the same reason [`KVStore::new`] is above.
+    pub fn with_cache_capacity(path: &str, cache_cap: usize) -> std::io::Result<Self> {
+        KVStore::with_options(path, cache_cap, default_ignored_names())
+    }
+
+    /// Initializes a KVStore the same way [`KVStore::new`] does, but lets the
+    /// caller configure both the read cache capacity and the list of filenames
+    /// skipped (at the top level and inside each bucket) when enumerating
+    /// buckets and counting existing pairs. Pinned to `DiskBackend` for the
+    /// same reason [`KVStore::new`] is above.
+    pub fn with_options(path: &str, cache_cap: usize, ignored_names: Vec<String>) -> std::io::Result<Self> {
+        KVStore::with_full_options(path, cache_cap, ignored_names, DiskBackend)
     }
 }
 
@@ -348,4 +805,133 @@ mod tests {
     fn hello_world_test() {
         assert_eq!(4, 4);
     }
-}
\ No newline at end of file
+
+    #[cfg(unix)]
+    #[test]
+    fn insert_restricts_key_and_value_files_to_owner_read_write() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().subsec_nanos();
+        let dir = std::env::temp_dir().join(format!("kvstore_test_perms_{:x}{:x}", std::process::id(), nanos));
+        let mut kvs = KVStore::new(dir.to_str().unwrap()).unwrap();
+        kvs.insert(String::from("a"), 1i32).unwrap();
+
+        let (key_path, value_path) = kvs.key_paths(&digest(&serde_json::to_string(&String::from("a")).unwrap()));
+        let key_mode = fs::metadata(&key_path).unwrap().permissions().mode() & 0o777;
+        let value_mode = fs::metadata(&value_path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(key_mode, 0o600);
+        assert_eq!(value_mode, 0o600);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn iter_returns_every_pair_with_correct_content() {
+        let mut kvs: KVStore<InMemoryBackend> =
+            KVStore::with_backend("/store", InMemoryBackend::default()).unwrap();
+        kvs.insert(String::from("a"), 1i32).unwrap();
+        kvs.insert(String::from("b"), 2i32).unwrap();
+
+        let mut pairs = kvs.iter::<String, i32>().unwrap();
+        pairs.sort();
+        assert_eq!(pairs, vec![(String::from("a"), 1), (String::from("b"), 2)]);
+    }
+
+    #[test]
+    fn cache_evicts_least_recently_used_entry() {
+        let mut kvs: KVStore<InMemoryBackend> =
+            KVStore::with_full_options("/store", 2, default_ignored_names(), InMemoryBackend::default()).unwrap();
+        let hash_a = digest(&serde_json::to_string(&String::from("a")).unwrap());
+        let hash_b = digest(&serde_json::to_string(&String::from("b")).unwrap());
+        let hash_c = digest(&serde_json::to_string(&String::from("c")).unwrap());
+
+        kvs.insert(String::from("a"), 1i32).unwrap();
+        kvs.insert(String::from("b"), 2i32).unwrap();
+        kvs.insert(String::from("c"), 3i32).unwrap();
+        // cap is 2, so inserting "c" should evict "a", the least recently used.
+        assert_eq!(kvs.cache.len(), 2);
+        assert!(!kvs.cache.contains_key(&hash_a));
+        assert!(kvs.cache.contains_key(&hash_b));
+        assert!(kvs.cache.contains_key(&hash_c));
+
+        // Touching "b" makes it the most recently used, so inserting "d" should
+        // evict "c" instead.
+        kvs.lookup::<String, i32>(String::from("b")).unwrap();
+        kvs.insert(String::from("d"), 4i32).unwrap();
+        assert!(kvs.cache.contains_key(&hash_b));
+        assert!(!kvs.cache.contains_key(&hash_c));
+    }
+
+    #[test]
+    fn new_counts_real_pairs_only_ignoring_foreign_and_leftover_files() {
+        // Reopening a directory that already has one real key-value pair, plus a
+        // foreign file that happens to contain ".key" as a substring and a
+        // dangling `<hash>.key.tmp...` leftover from a crash mid-insert (chunk0-4),
+        // should still report size() == 1, not 3.
+        let backend = InMemoryBackend::default();
+        let path = "/store";
+        backend.create_dir_all(Path::new(path)).unwrap();
+        let bucket_path = Path::new(path).join("abcdefghij");
+        backend.create_dir(&bucket_path).unwrap();
+        backend.write(&bucket_path.join("abcdefghij0123.key"), b"\"real\"").unwrap();
+        backend.write(&bucket_path.join("abcdefghij0123.value"), b"1").unwrap();
+        backend.write(&bucket_path.join("notes.keychain.bak"), b"junk").unwrap();
+        backend.write(&bucket_path.join("abcdefghij0123.key.tmp1a2b3c"), b"\"stale\"").unwrap();
+
+        let kvs: KVStore<InMemoryBackend> = KVStore::with_backend(path, backend).unwrap();
+        assert_eq!(kvs.size(), 1);
+    }
+
+    #[test]
+    fn in_memory_backend_insert_lookup_remove() {
+        let mut kvs: KVStore<InMemoryBackend> =
+            KVStore::with_backend("/store", InMemoryBackend::default()).unwrap();
+        kvs.insert(String::from("a"), 1i32).unwrap();
+        kvs.insert(String::from("b"), 2i32).unwrap();
+        assert_eq!(kvs.lookup::<String, i32>(String::from("a")).unwrap(), 1);
+        assert_eq!(kvs.lookup::<String, i32>(String::from("b")).unwrap(), 2);
+        assert_eq!(kvs.remove::<String, i32>(String::from("a")).unwrap(), 1);
+        assert!(kvs.lookup::<String, i32>(String::from("a")).is_err());
+        assert_eq!(kvs.keys().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn insert_never_makes_a_key_visible_without_its_value() {
+        // Every consumer (the AlreadyExists check in insert, new()'s counting,
+        // keys(), iter()) treats a visible `.key` file as meaning "this pair
+        // exists", so a `.value` must always be in place before its `.key` is.
+        let mut kvs: KVStore<InMemoryBackend> =
+            KVStore::with_backend("/store", InMemoryBackend::default()).unwrap();
+        kvs.insert(String::from("a"), 1i32).unwrap();
+        kvs.insert(String::from("b"), 2i32).unwrap();
+
+        for bucket_path in kvs.backend.read_dir(Path::new(&kvs.path)).unwrap() {
+            for entry_path in kvs.backend.read_dir(&bucket_path).unwrap() {
+                if entry_path.extension().and_then(|ext| ext.to_str()) == Some("key") {
+                    let value_path = entry_path.with_extension("value");
+                    assert!(
+                        kvs.backend.exists(&value_path),
+                        "{:?} is visible with no matching .value",
+                        entry_path
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn export_import_round_trip() {
+        let mut kvs: KVStore<InMemoryBackend> =
+            KVStore::with_backend("/store", InMemoryBackend::default()).unwrap();
+        kvs.insert(String::from("a"), 1i32).unwrap();
+        kvs.insert(String::from("b"), 2i32).unwrap();
+
+        let mut archive = Vec::new();
+        kvs.export(&mut archive).unwrap();
+
+        let mut imported = KVStore::import("/store", &archive[..], InMemoryBackend::default()).unwrap();
+        assert_eq!(imported.size(), 2);
+        assert_eq!(imported.lookup::<String, i32>(String::from("a")).unwrap(), 1);
+        assert_eq!(imported.lookup::<String, i32>(String::from("b")).unwrap(), 2);
+    }
+}